@@ -1,10 +1,13 @@
 // Complete Ollama client implementation from Marvinous project
 // Location: /home/matt/Marvinous/src/llm/client.rs
 
+use futures::Stream;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::sync::Mutex;
 
 #[derive(Error, Debug)]
 pub enum OllamaError {
@@ -14,11 +17,48 @@ pub enum OllamaError {
     GenerationError(String),
 }
 
+/// Sampling and context-window parameters, serialized under Ollama's
+/// `options` key
+#[derive(Serialize, Clone)]
+pub struct Options {
+    /// Context window size in tokens. Defaults to 4096 since Ollama
+    /// exposes no API to query a model's actual max; override for
+    /// long-context work.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            num_ctx: Some(4096),
+            temperature: None,
+            top_p: None,
+            seed: None,
+            stop: None,
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct GenerateRequest {
     model: String,
     prompt: String,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<Options>,
 }
 
 #[derive(Deserialize)]
@@ -31,12 +71,74 @@ struct GenerateResponse {
     context: Option<Vec<i64>>,
 }
 
+/// A single turn in a `chat` conversation
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Message {
+    /// One of "system", "user", or "assistant"
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<Message>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<Options>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    #[allow(dead_code)]
+    model: Option<String>,
+    message: Message,
+    #[allow(dead_code)]
+    done: bool,
+}
+
+#[derive(Serialize)]
+struct EmbedRequest {
+    model: String,
+    prompt: String,
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embedding: Vec<f32>,
+}
+
+/// An installed model as reported by `GET /api/tags`
+#[derive(Deserialize, Debug, Clone)]
+pub struct ModelInfo {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Deserialize)]
+struct TagsResponse {
+    models: Vec<ModelInfo>,
+}
+
 pub struct OllamaClient {
     client: Client,
     endpoint: String,
     model: String,
+    timeout_secs: u64,
+    headers: HeaderMap,
+    embedding_dim: Option<usize>,
+    max_requests_per_second: Option<f32>,
+    last_request: Mutex<Option<Instant>>,
+    max_retries: u32,
 }
 
+/// Base delay for the first retry; doubles on each subsequent attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Upper bound on the backoff delay, regardless of attempt count
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
 impl OllamaClient {
     /// Create a new Ollama client
     ///
@@ -54,35 +156,217 @@ impl OllamaClient {
             client,
             endpoint: endpoint.to_string(),
             model: model.to_string(),
+            timeout_secs,
+            headers: HeaderMap::new(),
+            embedding_dim: None,
+            max_requests_per_second: None,
+            last_request: Mutex::new(None),
+            max_retries: 0,
         }
     }
 
+    /// Record the expected embedding dimension for this client's model
+    /// (e.g. 768 for `nomic-embed-text`), so `embed`/`embed_many` can
+    /// validate their output length. Ollama does not report this itself
+    /// and different embedding models differ, so callers must supply it.
+    pub fn with_embedding_dim(mut self, dim: usize) -> Self {
+        self.embedding_dim = Some(dim);
+        self
+    }
+
+    /// Authenticate against a remote/proxied Ollama server with a bearer
+    /// token, sent as `Authorization: Bearer <token>` on every request
+    ///
+    /// # Panics
+    /// Panics if `token` contains characters that aren't valid in an HTTP
+    /// header value
+    pub fn with_bearer_token(mut self, token: &str) -> Self {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token))
+            .expect("bearer token is not a valid header value");
+        self.headers.insert(AUTHORIZATION, value);
+        self.rebuild_client()
+    }
+
+    /// Attach a custom header to every request, e.g. for a reverse proxy
+    /// that expects its own auth or routing header
+    ///
+    /// # Panics
+    /// Panics if `key`/`value` aren't valid HTTP header name/value
+    pub fn with_header(mut self, key: &str, value: &str) -> Self {
+        let name = HeaderName::from_bytes(key.as_bytes()).expect("invalid header name");
+        let value = HeaderValue::from_str(value).expect("invalid header value");
+        self.headers.insert(name, value);
+        self.rebuild_client()
+    }
+
+    fn rebuild_client(mut self) -> Self {
+        self.client = Client::builder()
+            .timeout(Duration::from_secs(self.timeout_secs))
+            .default_headers(self.headers.clone())
+            .build()
+            .expect("Failed to create HTTP client");
+        self
+    }
+
+    /// Cap outgoing `generate`/`chat`/`embed` calls to at most this many
+    /// requests per second, gating concurrent callers behind a shared
+    /// minimum-interval check. `0` (or never calling this) means
+    /// unlimited.
+    pub fn with_max_requests_per_second(mut self, max_requests_per_second: f32) -> Self {
+        self.max_requests_per_second = if max_requests_per_second > 0.0 {
+            Some(max_requests_per_second)
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Block until it's been at least `1 / max_requests_per_second`
+    /// seconds since the last call, if a rate limit is configured
+    async fn throttle(&self) {
+        let Some(rps) = self.max_requests_per_second else {
+            return;
+        };
+
+        let min_interval = Duration::from_secs_f32(1.0 / rps);
+        let mut last_request = self.last_request.lock().await;
+
+        if let Some(previous) = *last_request {
+            let elapsed = previous.elapsed();
+            if elapsed < min_interval {
+                tokio::time::sleep(min_interval - elapsed).await;
+            }
+        }
+
+        *last_request = Some(Instant::now());
+    }
+
+    /// Retry transient failures (connection errors, timeouts, 429/5xx)
+    /// up to `max_retries` times, waiting longer between attempts
+    /// (500ms, 1s, 2s, ... capped at 30s, or the server's `Retry-After`
+    /// if one is given). `0` (the default) means no retries.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Send a request built fresh from `request_fn` on each attempt,
+    /// retrying per `with_max_retries` on transient failures. Every
+    /// attempt - including retries - passes through `throttle`, so
+    /// `max_requests_per_second` bounds actual wire requests, not just
+    /// logical calls.
+    ///
+    /// # Errors
+    /// Returns error immediately for non-retryable failures (4xx other
+    /// than 429) or once retries are exhausted
+    async fn send_with_retry<F>(&self, request_fn: F) -> Result<reqwest::Response, OllamaError>
+    where
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        let mut attempt = 0;
+
+        loop {
+            self.throttle().await;
+
+            match request_fn().send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    if status.is_success() {
+                        return Ok(response);
+                    }
+
+                    let retryable = status.as_u16() == 429 || status.is_server_error();
+                    if !retryable || attempt >= self.max_retries {
+                        return Err(OllamaError::GenerationError(format!(
+                            "Ollama returned status {}",
+                            status
+                        )));
+                    }
+
+                    let delay = response
+                        .headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|s| s.parse::<u64>().ok())
+                        .map(Duration::from_secs)
+                        .unwrap_or_else(|| Self::backoff_delay(attempt));
+
+                    tracing::warn!(
+                        "Ollama request failed with {}, retrying in {:?} (attempt {}/{})",
+                        status,
+                        delay,
+                        attempt + 1,
+                        self.max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    let retryable = e.is_timeout() || e.is_connect();
+                    if !retryable || attempt >= self.max_retries {
+                        return Err(OllamaError::GenerationError(format!(
+                            "HTTP request failed: {}",
+                            e
+                        )));
+                    }
+
+                    let delay = Self::backoff_delay(attempt);
+                    tracing::warn!(
+                        "Ollama request failed ({}), retrying in {:?} (attempt {}/{})",
+                        e,
+                        delay,
+                        attempt + 1,
+                        self.max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn backoff_delay(attempt: u32) -> Duration {
+        (RETRY_BASE_DELAY * 2u32.saturating_pow(attempt)).min(RETRY_MAX_DELAY)
+    }
+
     /// Generate a completion from a prompt
     ///
     /// # Arguments
     /// * `prompt` - The text prompt to send to the model
+    /// * `system` - Optional system instruction overriding the model's default
+    /// * `format` - Optional output format, e.g. `Some("json")`
+    /// * `options` - Sampling/context-window overrides; `None` falls back to `Options::default()` (`num_ctx` 4096)
     ///
     /// # Returns
     /// The generated text response
     ///
     /// # Errors
     /// Returns error if network fails or response parsing fails
-    pub async fn generate(&self, prompt: &str) -> Result<String, OllamaError> {
+    pub async fn generate(
+        &self,
+        prompt: &str,
+        system: Option<&str>,
+        format: Option<&str>,
+        options: Option<Options>,
+    ) -> Result<String, OllamaError> {
         let request = GenerateRequest {
             model: self.model.clone(),
             prompt: prompt.to_string(),
             stream: false,
+            system: system.map(str::to_string),
+            format: format.map(str::to_string),
+            options: Some(options.unwrap_or_default()),
         };
 
         tracing::info!("Sending prompt to Ollama ({} chars)", prompt.len());
 
         let response = self
-            .client
-            .post(format!("{}/api/generate", self.endpoint))
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| OllamaError::GenerationError(format!("HTTP request failed: {}", e)))?;
+            .send_with_retry(|| {
+                self.client
+                    .post(format!("{}/api/generate", self.endpoint))
+                    .json(&request)
+            })
+            .await?;
 
         let result = response
             .json::<GenerateResponse>()
@@ -99,6 +383,219 @@ impl OllamaClient {
 
         Ok(result.response)
     }
+
+    /// Generate a completion, streaming response chunks as they arrive
+    ///
+    /// # Arguments
+    /// * `prompt` - The text prompt to send to the model
+    ///
+    /// # Returns
+    /// A stream yielding each incremental `response` fragment Ollama sends,
+    /// ending once the server reports `done: true`
+    ///
+    /// # Errors
+    /// Yields an error if the network connection fails or a streamed line
+    /// cannot be parsed as a `GenerateResponse`
+    pub fn generate_stream(
+        &self,
+        prompt: &str,
+    ) -> impl Stream<Item = Result<String, OllamaError>> + '_ {
+        let request = GenerateRequest {
+            model: self.model.clone(),
+            prompt: prompt.to_string(),
+            stream: true,
+            system: None,
+            format: None,
+            options: None,
+        };
+        let prompt_len = prompt.len();
+
+        async_stream::try_stream! {
+            use futures::StreamExt;
+
+            self.throttle().await;
+
+            tracing::info!("Sending streaming prompt to Ollama ({} chars)", prompt_len);
+
+            let response = self
+                .client
+                .post(format!("{}/api/generate", self.endpoint))
+                .json(&request)
+                .send()
+                .await
+                .map_err(|e| OllamaError::GenerationError(format!("HTTP request failed: {}", e)))?;
+
+            let mut byte_stream = response.bytes_stream();
+            let mut buffer: Vec<u8> = Vec::new();
+
+            'outer: while let Some(chunk) = byte_stream.next().await {
+                let chunk = chunk
+                    .map_err(|e| OllamaError::GenerationError(format!("Stream read failed: {}", e)))?;
+                buffer.extend_from_slice(&chunk);
+
+                while let Some(newline_pos) = buffer.iter().position(|&b| b == b'\n') {
+                    let mut line: Vec<u8> = buffer.drain(..=newline_pos).collect();
+                    line.pop();
+
+                    if line.iter().all(|b| b.is_ascii_whitespace()) {
+                        continue;
+                    }
+
+                    let parsed: GenerateResponse = serde_json::from_slice(&line).map_err(|e| {
+                        OllamaError::GenerationError(format!(
+                            "Failed to parse streamed response: {}",
+                            e
+                        ))
+                    })?;
+
+                    if parsed.done {
+                        break 'outer;
+                    }
+
+                    yield parsed.response;
+                }
+            }
+        }
+    }
+
+    /// Run a multi-turn conversation, optionally including a system prompt
+    ///
+    /// # Arguments
+    /// * `messages` - The conversation history, oldest first (a leading
+    ///   `role: "system"` message sets the persona/instructions)
+    /// * `format` - Optional output format, e.g. `Some("json")`
+    /// * `options` - Sampling/context-window overrides; `None` falls back to `Options::default()` (`num_ctx` 4096)
+    ///
+    /// # Returns
+    /// The assistant's reply as a `Message`
+    ///
+    /// # Errors
+    /// Returns error if network fails or response parsing fails
+    pub async fn chat(
+        &self,
+        messages: &[Message],
+        format: Option<&str>,
+        options: Option<Options>,
+    ) -> Result<Message, OllamaError> {
+        let request = ChatRequest {
+            model: self.model.clone(),
+            messages: messages.to_vec(),
+            stream: false,
+            format: format.map(str::to_string),
+            options: Some(options.unwrap_or_default()),
+        };
+
+        tracing::info!("Sending chat request to Ollama ({} messages)", messages.len());
+
+        let response = self
+            .send_with_retry(|| {
+                self.client
+                    .post(format!("{}/api/chat", self.endpoint))
+                    .json(&request)
+            })
+            .await?;
+
+        let result = response
+            .json::<ChatResponse>()
+            .await
+            .map_err(|e| OllamaError::GenerationError(format!("Failed to parse response: {}", e)))?;
+
+        tracing::info!("Response received ({} chars)", result.message.content.len());
+
+        Ok(result.message)
+    }
+
+    /// Get an embedding vector for a single piece of text
+    ///
+    /// # Arguments
+    /// * `input` - The text to embed
+    ///
+    /// # Returns
+    /// The embedding as returned by Ollama, validated against
+    /// `embedding_dim` if one was configured via `with_embedding_dim`
+    ///
+    /// # Errors
+    /// Returns error if network fails, response parsing fails, or the
+    /// returned vector doesn't match the configured dimension
+    pub async fn embed(&self, input: &str) -> Result<Vec<f32>, OllamaError> {
+        let request = EmbedRequest {
+            model: self.model.clone(),
+            prompt: input.to_string(),
+        };
+
+        let response = self
+            .send_with_retry(|| {
+                self.client
+                    .post(format!("{}/api/embeddings", self.endpoint))
+                    .json(&request)
+            })
+            .await?;
+
+        let result = response
+            .json::<EmbedResponse>()
+            .await
+            .map_err(|e| OllamaError::GenerationError(format!("Failed to parse response: {}", e)))?;
+
+        if let Some(expected) = self.embedding_dim {
+            if result.embedding.len() != expected {
+                return Err(OllamaError::GenerationError(format!(
+                    "Expected embedding of dimension {}, got {}",
+                    expected,
+                    result.embedding.len()
+                )));
+            }
+        }
+
+        Ok(result.embedding)
+    }
+
+    /// Get embedding vectors for a batch of texts
+    ///
+    /// # Arguments
+    /// * `inputs` - The texts to embed, in order
+    ///
+    /// # Returns
+    /// One embedding per input, in the same order
+    ///
+    /// # Errors
+    /// Returns the first error encountered; see `embed`
+    pub async fn embed_many(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, OllamaError> {
+        let mut embeddings = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            embeddings.push(self.embed(input).await?);
+        }
+        Ok(embeddings)
+    }
+
+    /// List the models currently installed on the Ollama server
+    ///
+    /// # Returns
+    /// The installed models' names and on-disk sizes
+    ///
+    /// # Errors
+    /// Returns error if the server is unreachable or the response can't
+    /// be parsed
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, OllamaError> {
+        let response = self
+            .send_with_retry(|| self.client.get(format!("{}/api/tags", self.endpoint)))
+            .await?;
+
+        let result = response
+            .json::<TagsResponse>()
+            .await
+            .map_err(|e| OllamaError::GenerationError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(result.models)
+    }
+
+    /// Check whether the Ollama server is up and responding
+    ///
+    /// Treats a successful `/api/tags` response as "server running" -
+    /// useful to surface a clear error instead of a silent timeout while
+    /// a model loads into memory.
+    pub async fn is_available(&self) -> bool {
+        self.list_models().await.is_ok()
+    }
 }
 
 // Example usage in async main:
@@ -112,7 +609,7 @@ impl OllamaClient {
 //     );
 //
 //     let prompt = "Analyze this server data and identify issues...";
-//     let response = client.generate(prompt).await?;
+//     let response = client.generate(prompt, None, None, None).await?;
 //
 //     println!("LLM Response:\n{}", response);
 //     Ok(())